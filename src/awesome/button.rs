@@ -1,16 +1,112 @@
 use std::fmt::{self, Display, Formatter};
 use std::default::Default;
-use rlua::{self, Table, Lua, UserData, ToLua, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use rlua::{self, Table, Lua, UserData, ToLua, FromLua, Value, Function, RegistryKey, Variadic};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::ser::SerializeStruct;
 use super::object::{Object, Objectable};
 use super::property::Property;
 use super::class::{self, Class};
-use rustwlc::types::KeyMod;
+use rustwlc::types::{KeyMod, MOD_SHIFT, MOD_CAPS, MOD_CTRL, MOD_ALT,
+                      MOD_MOD2, MOD_MOD3, MOD_LOGO, MOD_MOD5};
 use xcb::ffi::xproto::xcb_button_t;
 
+static NEXT_SIGNAL_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The Rust-side signal registry `connect_signal`/`disconnect_signal`/
+    /// `emit_signal` back onto, keyed by each button's private
+    /// `signal_id` rather than by the object's table (a `RegistryKey`
+    /// alone doesn't give us the identity/hashing needed to use the
+    /// object itself as a key). Callbacks are stored via
+    /// `lua.create_registry_value`, so they're immune to Lua GC and can
+    /// be called straight from native code without going through
+    /// `lua.globals()`.
+    static SIGNALS: RefCell<HashMap<usize, HashMap<String, Vec<RegistryKey>>>> =
+        RefCell::new(HashMap::new());
+}
+
 #[derive(Clone, Debug)]
 pub struct ButtonState {
     button: xcb_button_t,
-    modifiers: KeyMod
+    modifiers: KeyMod,
+    signal_id: usize
+}
+
+impl PartialEq for ButtonState {
+    /// Value equality only ever looks at `button`/`modifiers` -- two
+    /// buttons can compare equal (see `__eq`) without sharing a signal
+    /// registry entry.
+    fn eq(&self, other: &Self) -> bool {
+        self.button == other.button && self.modifiers == other.modifiers
+    }
+}
+
+/// The stable modifier names used for `KeyMod` (de)serialization. These
+/// match the names `set_modifiers`/`mods_to_lua` already expose to Lua
+/// (e.g. `"Caps"`), so a snapshot taken over IPC can be fed straight back
+/// into `a_button.modifiers = { ... }`.
+const MOD_NAMES: &'static [(KeyMod, &'static str)] = &[
+    (MOD_SHIFT, "Shift"),
+    (MOD_CAPS, "Caps"),
+    (MOD_CTRL, "Ctrl"),
+    (MOD_ALT, "Alt"),
+    (MOD_MOD2, "Mod2"),
+    (MOD_MOD3, "Mod3"),
+    (MOD_LOGO, "Logo"),
+    (MOD_MOD5, "Mod5")
+];
+
+fn mod_names(mods: KeyMod) -> Vec<String> {
+    MOD_NAMES.iter()
+        .filter(|&&(flag, _)| mods.contains(flag))
+        .map(|&(_, name)| name.to_string())
+        .collect()
+}
+
+fn mods_from_names(names: &[String]) -> KeyMod {
+    let mut mods = KeyMod::empty();
+    for name in names {
+        if let Some(&(flag, _)) = MOD_NAMES.iter().find(|&&(_, n)| n == name) {
+            mods |= flag;
+        }
+    }
+    mods
+}
+
+impl Serialize for ButtonState {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let mut state = serializer.serialize_struct("ButtonState", 2)?;
+        state.serialize_field("button", &self.button)?;
+        state.serialize_field("modifiers", &mod_names(self.modifiers))?;
+        state.end()
+    }
+}
+
+#[derive(Deserialize)]
+struct ButtonStateRepr {
+    button: xcb_button_t,
+    modifiers: Vec<String>
+}
+
+impl<'de> Deserialize<'de> for ButtonState {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let repr = ButtonStateRepr::deserialize(deserializer)?;
+        Ok(ButtonState {
+            button: repr.button,
+            modifiers: mods_from_names(&repr.modifiers),
+            // Restored buttons are fresh objects as far as signal
+            // handlers go -- `signal_id` is Rust-only bookkeeping and
+            // was never part of the snapshot.
+            signal_id: NEXT_SIGNAL_ID.fetch_add(1, Ordering::Relaxed)
+        })
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -41,6 +137,103 @@ impl <'lua> Button<'lua> {
         self.0.set("data", button)?;
         Ok(())
     }
+
+    fn signal_id(&self) -> rlua::Result<usize> {
+        Ok(self.0.get::<_, ButtonState>("data")?.signal_id)
+    }
+
+    /// Stores `func` in the Rust-side registry under `signal`, keyed by
+    /// this button's `signal_id`, via `lua.create_registry_value`.
+    pub fn connect_signal(&self, lua: &'lua Lua, signal: &str, func: Function<'lua>)
+                          -> rlua::Result<()> {
+        let id = self.signal_id()?;
+        let key = lua.create_registry_value(func)?;
+        SIGNALS.with(|signals| {
+            signals.borrow_mut()
+                .entry(id).or_insert_with(HashMap::new)
+                .entry(signal.to_string()).or_insert_with(Vec::new)
+                .push(key);
+        });
+        Ok(())
+    }
+
+    /// Removes every handler connected to `signal` on this button,
+    /// `remove_registry_value`-ing each key so Lua can collect them.
+    pub fn disconnect_signal(&self, lua: &'lua Lua, signal: &str) -> rlua::Result<()> {
+        let id = self.signal_id()?;
+        let removed = SIGNALS.with(|signals| {
+            signals.borrow_mut().get_mut(&id).and_then(|sigs| sigs.remove(signal))
+        });
+        if let Some(keys) = removed {
+            for key in keys {
+                lua.remove_registry_value(key)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Emits `signal` on this button with `args`, without first having to
+    /// find the object through `lua.globals()`. Connected handlers are
+    /// resolved from the Rust-side `RegistryKey` signal registry, so this
+    /// is safe to call straight from native input handlers (e.g. a pointer
+    /// button event coming up from rustwlc).
+    ///
+    /// `args` is flattened via `ToLuaMulti` rather than passed through as
+    /// a single nested value, so e.g. `emit_signal(lua, "press", (x, y))`
+    /// calls handlers as `function(button, x, y)`, matching what
+    /// `lua_emit_signal`'s `Variadic<Value>` already forwards from Lua.
+    pub fn emit_signal<A>(&self, lua: &'lua Lua, signal: &str, args: A) -> rlua::Result<()>
+        where A: rlua::ToLuaMulti<'lua>
+    {
+        let id = self.signal_id()?;
+        let funcs: Vec<Function> = SIGNALS.with(|signals| -> rlua::Result<_> {
+            let signals = signals.borrow();
+            match signals.get(&id).and_then(|sigs| sigs.get(signal)) {
+                Some(keys) => keys.iter()
+                    .map(|key| lua.registry_value::<Function>(key))
+                    .collect(),
+                None => Ok(Vec::new())
+            }
+        })?;
+        if funcs.is_empty() {
+            return Ok(());
+        }
+        let mut call_args = vec![self.clone().to_lua(lua)?];
+        call_args.extend(args.to_lua_multi(lua)?);
+        for func in funcs {
+            func.call::<_, ()>(Variadic(call_args.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// This button's snapshot in the shape the IPC object-tree dump uses
+    /// for every live object: `ButtonState`'s own JSON (`button`,
+    /// `modifiers`) plus a `"class"` tag so a dump spanning many object
+    /// types can tell them apart. `apply_json` is the inverse.
+    pub fn to_json(&self) -> rlua::Result<serde_json::Value> {
+        let state = self.0.get::<_, ButtonState>("data")?;
+        let mut snapshot = serde_json::to_value(&state)
+            .map_err(|e| rlua::Error::RuntimeError(
+                format!("failed to serialize button: {}", e)))?;
+        if let serde_json::Value::Object(ref mut map) = snapshot {
+            map.insert("class".to_string(),
+                       serde_json::Value::String("button".to_string()));
+        }
+        Ok(snapshot)
+    }
+
+    /// Restores a `to_json` snapshot onto this button, e.g. to replay an
+    /// IPC dump back into a live object. Keeps this button's own
+    /// `signal_id`, so handlers connected before the restore stay
+    /// connected afterward.
+    pub fn apply_json(&self, snapshot: &serde_json::Value) -> rlua::Result<()> {
+        let mut state: ButtonState = serde_json::from_value(snapshot.clone())
+            .map_err(|e| rlua::Error::RuntimeError(
+                format!("failed to restore button: {}", e)))?;
+        state.signal_id = self.signal_id()?;
+        self.0.set("data", state)?;
+        Ok(())
+    }
 }
 
 impl <'lua> ToLua<'lua> for Button<'lua> {
@@ -49,6 +242,61 @@ impl <'lua> ToLua<'lua> for Button<'lua> {
     }
 }
 
+impl <'lua> FromLua<'lua> for Button<'lua> {
+    fn from_lua(value: Value<'lua>, _lua: &'lua Lua) -> rlua::Result<Self> {
+        let table = match value {
+            Value::Table(table) => table,
+            value => return Err(rlua::Error::FromLuaConversionError {
+                from: value_type_name(&value),
+                to: "Button",
+                message: Some("expected a button object".into())
+            })
+        };
+        // Objectable::cast does the same "is this really a button" checks
+        // (the "data" field holds a ButtonState, the table is stamped with
+        // the button class metatable) that the old `Button::cast` call
+        // sites were doing by hand.
+        Button::cast(table.into()).map_err(|_| rlua::Error::FromLuaConversionError {
+            from: "table",
+            to: "Button",
+            message: Some("table is not a button object".into())
+        })
+    }
+}
+
+impl <'lua> FromLua<'lua> for Object<'lua> {
+    fn from_lua(value: Value<'lua>, _lua: &'lua Lua) -> rlua::Result<Self> {
+        match value {
+            // Object is the untyped base wrapper every awesome object
+            // table converts into (the same `table.into()` the old
+            // `Button::cast(table.into())?` call sites already relied
+            // on) -- no class-specific checks belong here, those happen
+            // when something more specific (e.g. Button) casts it.
+            Value::Table(table) => Ok(table.into()),
+            value => Err(rlua::Error::FromLuaConversionError {
+                from: value_type_name(&value),
+                to: "Object",
+                message: Some("expected an awesome object table".into())
+            })
+        }
+    }
+}
+
+fn value_type_name(value: &Value) -> &'static str {
+    match *value {
+        Value::Nil => "nil",
+        Value::Boolean(_) => "boolean",
+        Value::Integer(_) => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Table(_) => "table",
+        Value::Function(_) => "function",
+        Value::UserData(_) => "userdata",
+        Value::LightUserData(_) => "light userdata",
+        Value::Error(_) => "error"
+    }
+}
+
 impl <'lua> Objectable<'lua, Button<'lua>, ButtonState> for Button<'lua> {
     fn _wrap(table: Table<'lua>) -> Button {
         Button(table)
@@ -69,23 +317,154 @@ impl Default for ButtonState {
     fn default() -> Self {
         ButtonState {
             button: xcb_button_t::default(),
-            modifiers: KeyMod::empty()
+            modifiers: KeyMod::empty(),
+            signal_id: NEXT_SIGNAL_ID.fetch_add(1, Ordering::Relaxed)
         }
     }
 }
 
 impl UserData for ButtonState {}
 
+/// `awful.button`-style dispatch: the set of `Button` objects a pointer
+/// button event should be checked against. Buttons are held by
+/// `RegistryKey`, keyed by `signal_id`, so the registry isn't tied to any
+/// one `'lua` borrow, can live alongside the compositor's input state,
+/// and a button can be pulled back out again by `forget_button`.
+#[derive(Default)]
+pub struct ButtonRegistry {
+    buttons: HashMap<usize, rlua::RegistryKey>
+}
+
+impl ButtonRegistry {
+    pub fn new() -> Self {
+        ButtonRegistry { buttons: HashMap::new() }
+    }
+
+    /// Registers `button` so it is considered by future `on_press`/
+    /// `on_release` dispatches.
+    pub fn register(&mut self, lua: &Lua, button: &Button) -> rlua::Result<()> {
+        let id = button.signal_id()?;
+        let key = lua.create_registry_value(button.clone().0)?;
+        self.buttons.insert(id, key);
+        Ok(())
+    }
+
+    /// Removes `id`'s entry, returning its `RegistryKey` so the caller can
+    /// free it from the Lua registry.
+    pub fn unregister(&mut self, id: usize) -> Option<rlua::RegistryKey> {
+        self.buttons.remove(&id)
+    }
+
+    /// Emits `"press"` on every registered button whose `button` index
+    /// matches `event_button` and whose `modifiers` are a subset of
+    /// `held_mods`.
+    pub fn on_press(&self, lua: &Lua, event_button: xcb_button_t,
+                     held_mods: KeyMod, x: i32, y: i32) -> rlua::Result<()> {
+        self.dispatch(lua, event_button, held_mods, "press", x, y)
+    }
+
+    /// Emits `"release"` on every registered button whose `button` index
+    /// matches `event_button` and whose `modifiers` are a subset of
+    /// `held_mods`.
+    pub fn on_release(&self, lua: &Lua, event_button: xcb_button_t,
+                       held_mods: KeyMod, x: i32, y: i32) -> rlua::Result<()> {
+        self.dispatch(lua, event_button, held_mods, "release", x, y)
+    }
+
+    fn dispatch(&self, lua: &Lua, event_button: xcb_button_t, held_mods: KeyMod,
+                signal: &str, x: i32, y: i32) -> rlua::Result<()> {
+        for key in self.buttons.values() {
+            let table: Table = lua.registry_value(key)?;
+            let button = Button::cast(table.into())?;
+            let matches_button = match button.button()? {
+                Value::Integer(idx) => idx as xcb_button_t == event_button,
+                _ => false
+            };
+            if !matches_button {
+                continue;
+            }
+            if !held_mods.contains(button.modifiers()?) {
+                continue;
+            }
+            button.emit_signal(lua, signal, (x, y))?;
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    /// The process-wide registry a wired-up rustwlc pointer callback
+    /// dispatches through. `allocator` registers every button it
+    /// creates; `forget_button` is the matching removal path.
+    static POINTER_BUTTONS: RefCell<ButtonRegistry> = RefCell::new(ButtonRegistry::new());
+}
+
+/// Registers `button` with the process-wide pointer-button registry, so
+/// a wired-up rustwlc callback will consider it for future press/release
+/// dispatch. Called automatically by `allocator`.
+pub fn register_for_pointer_events(lua: &Lua, button: &Button) -> rlua::Result<()> {
+    POINTER_BUTTONS.with(|registry| registry.borrow_mut().register(lua, button))
+}
+
+/// The single call a rustwlc pointer-button callback should make: translate
+/// whatever the compositor/libinput reports for the event into these five
+/// values (the xcb button index, the held `KeyMod`s, whether this is a
+/// press or a release, and the pointer position) and forward them here.
+/// Registering the matching `extern "C"` callback with rustwlc's interface
+/// table is the remaining piece outside this tree.
+pub fn dispatch_pointer_button(lua: &Lua, event_button: xcb_button_t, held_mods: KeyMod,
+                                pressed: bool, x: i32, y: i32) -> rlua::Result<()> {
+    POINTER_BUTTONS.with(|registry| {
+        let registry = registry.borrow();
+        if pressed {
+            registry.on_press(lua, event_button, held_mods, x, y)
+        } else {
+            registry.on_release(lua, event_button, held_mods, x, y)
+        }
+    })
+}
+
+/// Removes `id` from both the signal (`SIGNALS`) and pointer-event
+/// (`POINTER_BUTTONS`) registries, freeing their `RegistryKey`s. Call this
+/// once a button is actually being discarded -- e.g. before dropping the
+/// last Lua reference to it -- so neither registry grows without bound.
+/// Not automatic: every getter on `Button` goes through a cloned
+/// `ButtonState` (see `signal_id`), so a `Drop` tied to that clone's
+/// lifetime would fire on every property read, not just real disposal.
+pub fn forget_button(lua: &Lua, id: usize) -> rlua::Result<()> {
+    let removed = SIGNALS.with(|signals| signals.borrow_mut().remove(&id));
+    if let Some(sigs) = removed {
+        for (_, keys) in sigs {
+            for key in keys {
+                lua.remove_registry_value(key)?;
+            }
+        }
+    }
+    if let Some(key) = POINTER_BUTTONS.with(|registry| registry.borrow_mut().unregister(id)) {
+        lua.remove_registry_value(key)?;
+    }
+    Ok(())
+}
+
 /// Makes a new button stored in a table beside its signals
 pub fn allocator(lua: &Lua) -> rlua::Result<Object> {
     let meta = lua.create_table();
+    // __eq/__tostring are resolved by a raw lookup on each instance's own
+    // metatable, not through __index, so they're set here rather than
+    // via Class::new/.method() (which only reaches the class's shared
+    // method table).
+    meta.set("__tostring", lua.create_function(button_tostring))?;
+    meta.set("__eq", lua.create_function(button_eq))?;
     let class = class::button_class(lua)?;
-    Ok(Button::new(lua, class)?
-       .add_to_meta(meta)?
-       .build())
+    let object = Button::new(lua, class)?
+        .add_to_meta(meta)?
+        .build();
+    let button = Button::cast(object)?;
+    register_for_pointer_events(lua, &button)?;
+    Ok(button.get_table().into())
 }
 
-pub fn new<'lua>(lua: &'lua Lua, _table: Table<'lua>)
+pub fn new<'lua>(lua: &'lua Lua, _obj: Object<'lua>)
                  -> rlua::Result<Object<'lua>> {
     allocator(lua)
 }
@@ -94,6 +473,9 @@ pub fn new<'lua>(lua: &'lua Lua, _table: Table<'lua>)
 pub fn init(lua: &Lua) -> rlua::Result<Class> {
     Class::new(lua, Some(allocator), None, None)?
         .method("__call".into(), lua.create_function(new))?
+        .method("connect_signal".into(), lua.create_function(lua_connect_signal))?
+        .method("disconnect_signal".into(), lua.create_function(lua_disconnect_signal))?
+        .method("emit_signal".into(), lua.create_function(lua_emit_signal))?
         .property(Property::new("button".into(),
                                 Some(lua.create_function(set_button)),
                                 Some(lua.create_function(get_button)),
@@ -106,13 +488,9 @@ pub fn init(lua: &Lua) -> rlua::Result<Class> {
         .build()
 }
 
-// TODO Try to see if I can make this pass in an Object,
-// or even better a Button
-
-fn set_button<'lua>(_: &'lua Lua, (table, val): (Table, Value))
+fn set_button<'lua>(_: &'lua Lua, (button, val): (Button<'lua>, Value))
                     -> rlua::Result<Value<'lua>> {
     use rlua::Value::*;
-    let button = Button::cast(table.into())?;
     match val {
         Number(num) => button.set_button(num as _)?,
         Integer(num) => button.set_button(num as _)?,
@@ -121,22 +499,54 @@ fn set_button<'lua>(_: &'lua Lua, (table, val): (Table, Value))
     Ok(Value::Nil)
 }
 
-fn get_button<'lua>(_: &'lua Lua, table: Table<'lua>)
+fn get_button<'lua>(_: &'lua Lua, button: Button<'lua>)
                     -> rlua::Result<Value<'lua>> {
-    Button::cast(table.into())?.button()
+    button.button()
 }
 
-fn set_modifiers<'lua>(_: &'lua Lua, (table, modifiers): (Table, Table))
+fn set_modifiers<'lua>(_: &'lua Lua, (button, modifiers): (Button<'lua>, Table))
                        -> rlua::Result<Value<'lua>> {
-    let button = Button::cast(table.into())?;
     button.set_modifiers(modifiers)?;
     Ok(Value::Nil)
 }
 
-fn get_modifiers<'lua>(lua: &'lua Lua, table: Table<'lua>)
+fn get_modifiers<'lua>(lua: &'lua Lua, button: Button<'lua>)
                     -> rlua::Result<Value<'lua>> {
     use ::lua::mods_to_lua;
-    mods_to_lua(lua, Button::cast(table.into())?.modifiers()?).map(Value::Table)
+    mods_to_lua(lua, button.modifiers()?).map(Value::Table)
+}
+
+fn button_tostring<'lua>(_: &'lua Lua, button: Button<'lua>)
+                        -> rlua::Result<String> {
+    let state = button.0.get::<_, ButtonState>("data")?;
+    Ok(format!("{}", state))
+}
+
+fn button_eq<'lua>(_: &'lua Lua, (left, right): (Button<'lua>, Button<'lua>))
+                   -> rlua::Result<bool> {
+    let left_state = left.0.get::<_, ButtonState>("data")?;
+    let right_state = right.0.get::<_, ButtonState>("data")?;
+    Ok(left_state == right_state)
+}
+
+fn lua_connect_signal<'lua>(lua: &'lua Lua,
+                            (button, signal, func): (Button<'lua>, String, Function<'lua>))
+                            -> rlua::Result<Value<'lua>> {
+    button.connect_signal(lua, &signal, func)?;
+    Ok(Value::Nil)
+}
+
+fn lua_disconnect_signal<'lua>(lua: &'lua Lua, (button, signal): (Button<'lua>, String))
+                               -> rlua::Result<Value<'lua>> {
+    button.disconnect_signal(lua, &signal)?;
+    Ok(Value::Nil)
+}
+
+fn lua_emit_signal<'lua>(lua: &'lua Lua,
+                         (button, signal, args): (Button<'lua>, String, Variadic<Value<'lua>>))
+                         -> rlua::Result<Value<'lua>> {
+    button.emit_signal(lua, &signal, args)?;
+    Ok(Value::Nil)
 }
 
 #[cfg(test)]
@@ -219,6 +629,104 @@ assert(button0.button == 0)
  "#, None).unwrap()
     }
 
+    #[test]
+    /// `Button::emit_signal` lets native (Rust) code drive a button's
+    /// handlers the same way `a_button.emit_signal(...)` does from Lua.
+    fn button_emit_signal_from_rust() {
+        use self::button::Button;
+        use self::object::Objectable;
+
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        lua.globals().set("a_button", button::allocator(&lua).unwrap());
+        let button = Button::cast(lua.globals().get::<_, Table>("a_button")
+                                  .unwrap().into()).unwrap();
+        lua.eval::<()>(r#"
+a_button.connect_signal("test", function(button, num) button.button = num end)
+"#, None).unwrap();
+        button.emit_signal(&lua, "test", 7).unwrap();
+        assert_eq!(button.button().unwrap(), rlua::Value::Integer(7));
+    }
+
+    #[test]
+    /// `connect_signal`/`disconnect_signal`/`emit_signal` are now backed
+    /// by the Rust-side RegistryKey registry rather than a Lua-side
+    /// table, but should keep the same observable semantics the Lua
+    /// tests above rely on -- including from the Rust side directly.
+    fn button_signal_registry_round_trip() {
+        use self::button::Button;
+        use self::object::Objectable;
+
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        lua.globals().set("a_button", button::allocator(&lua).unwrap());
+        let button = Button::cast(lua.globals().get::<_, Table>("a_button")
+                                  .unwrap().into()).unwrap();
+
+        let func = lua.create_function(|_, (button, num): (Button, i32)| {
+            button.set_button(num as _)
+        });
+        button.connect_signal(&lua, "test", func).unwrap();
+        button.emit_signal(&lua, "test", 9).unwrap();
+        assert_eq!(button.button().unwrap(), rlua::Value::Integer(9));
+
+        button.set_button(0).unwrap();
+        button.disconnect_signal(&lua, "test").unwrap();
+        button.emit_signal(&lua, "test", 9).unwrap();
+        assert_eq!(button.button().unwrap(), rlua::Value::Integer(0));
+    }
+
+    #[test]
+    /// `emit_signal`'s args must be flattened, not wrapped in a single
+    /// nested value -- a handler connected with N parameters after
+    /// `button` should see N separate arguments.
+    fn button_emit_signal_forwards_args_separately() {
+        use self::button::Button;
+        use self::object::Objectable;
+
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        lua.globals().set("a_button", button::allocator(&lua).unwrap());
+        let button = Button::cast(lua.globals().get::<_, Table>("a_button")
+                                  .unwrap().into()).unwrap();
+
+        let func = lua.create_function(|_, (button, x, y): (Button, i32, i32)| {
+            button.set_button((x + y) as _)
+        });
+        button.connect_signal(&lua, "test", func).unwrap();
+        button.emit_signal(&lua, "test", (3, 4)).unwrap();
+        assert_eq!(button.button().unwrap(), rlua::Value::Integer(7));
+    }
+
+    #[test]
+    /// `connect_signal` called from Lua must populate the same `SIGNALS`
+    /// registry `emit_signal` reads from -- not some other table reached
+    /// through `__index` -- otherwise `.method()` would merely shadow a
+    /// pre-existing signal mechanism rather than replace it.
+    fn lua_connect_signal_populates_rust_registry() {
+        use self::button::Button;
+        use self::object::Objectable;
+        use super::super::button::SIGNALS;
+
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        lua.globals().set("a_button", button::allocator(&lua).unwrap());
+        let button = Button::cast(lua.globals().get::<_, Table>("a_button")
+                                  .unwrap().into()).unwrap();
+        let id = button.signal_id().unwrap();
+
+        lua.eval::<()>(r#"
+a_button.connect_signal("test", function(button) end)
+"#, None).unwrap();
+
+        SIGNALS.with(|signals| {
+            let count = signals.borrow().get(&id)
+                .and_then(|sigs| sigs.get("test"))
+                .map(Vec::len).unwrap_or(0);
+            assert_eq!(count, 1);
+        });
+    }
+
     #[test]
     fn button_modifiers_test() {
         use rustwlc::*;
@@ -236,6 +744,203 @@ a_button.modifiers = { "Caps" }
         assert_eq!(button.modifiers().unwrap(), MOD_CAPS);
     }
 
+    #[test]
+    /// Synthesizes a pointer button press/release and checks that only
+    /// the button whose index and modifiers match actually fires.
+    fn button_registry_dispatch_test() {
+        use rustwlc::*;
+        use self::button::{Button, ButtonRegistry};
+        use self::object::Objectable;
+
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+
+        lua.globals().set("matching", button::allocator(&lua).unwrap());
+        lua.globals().set("other", button::allocator(&lua).unwrap());
+        lua.eval::<()>(r#"
+matching.button = 2
+matching.modifiers = { "Caps" }
+other.button = 3
+other.modifiers = { "Caps" }
+pressed = {}
+released = {}
+matching.connect_signal("press", function(button, x, y) pressed = { x, y } end)
+matching.connect_signal("release", function(button, x, y) released = { x, y } end)
+other.connect_signal("press", function(button, x, y) pressed = "other" end)
+"#, None).unwrap();
+
+        let matching = Button::cast(lua.globals().get::<_, Table>("matching")
+                                    .unwrap().into()).unwrap();
+        let other = Button::cast(lua.globals().get::<_, Table>("other")
+                                 .unwrap().into()).unwrap();
+
+        let mut registry = ButtonRegistry::new();
+        registry.register(&lua, &matching).unwrap();
+        registry.register(&lua, &other).unwrap();
+
+        registry.on_press(&lua, 2, MOD_CAPS, 10, 20).unwrap();
+        let pressed: Vec<i64> = lua.globals().get("pressed").unwrap();
+        assert_eq!(pressed, vec![10, 20]);
+
+        registry.on_release(&lua, 2, MOD_CAPS, 30, 40).unwrap();
+        let released: Vec<i64> = lua.globals().get("released").unwrap();
+        assert_eq!(released, vec![30, 40]);
+    }
+
+    #[test]
+    /// `allocator` registers every button it creates with the process-wide
+    /// registry, so `dispatch_pointer_button` -- the one call a real
+    /// rustwlc pointer-button callback would make -- finds it without any
+    /// extra registration step here.
+    fn pointer_event_dispatches_through_process_wide_registry() {
+        use rustwlc::*;
+        use self::button::Button;
+        use self::object::Objectable;
+
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        lua.globals().set("a_button", button::allocator(&lua).unwrap());
+        lua.eval::<()>(r#"
+a_button.button = 1
+a_button.modifiers = { "Caps" }
+pressed = nil
+a_button.connect_signal("press", function(button, x, y) pressed = { x, y } end)
+"#, None).unwrap();
+
+        button::dispatch_pointer_button(&lua, 1, MOD_CAPS, true, 5, 6).unwrap();
+
+        let pressed: Vec<i64> = lua.globals().get("pressed").unwrap();
+        assert_eq!(pressed, vec![5, 6]);
+    }
+
+    #[test]
+    /// `forget_button` is the explicit removal path for a button's
+    /// Rust-side bookkeeping: once called, neither SIGNALS nor
+    /// POINTER_BUTTONS should still hold an entry for it.
+    fn forget_button_clears_both_registries() {
+        use self::button::Button;
+        use self::object::Objectable;
+        use super::super::button::{SIGNALS, POINTER_BUTTONS};
+
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        lua.globals().set("a_button", button::allocator(&lua).unwrap());
+        let button = Button::cast(lua.globals().get::<_, Table>("a_button")
+                                  .unwrap().into()).unwrap();
+        let id = button.signal_id().unwrap();
+        button.connect_signal(&lua, "test", lua.create_function(|_, ()| Ok(()))).unwrap();
+
+        button::forget_button(&lua, id).unwrap();
+
+        SIGNALS.with(|signals| assert!(!signals.borrow().contains_key(&id)));
+        POINTER_BUTTONS.with(|registry| assert!(!registry.borrow().buttons.contains_key(&id)));
+    }
+
+    #[test]
+    fn button_eq_and_tostring_test() {
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        lua.globals().set("button0", button::allocator(&lua).unwrap());
+        lua.globals().set("button1", button::allocator(&lua).unwrap());
+        lua.eval::<()>(r#"
+assert(button0 == button1)
+button0.button = 3
+assert(button0 ~= button1)
+button1.button = 3
+assert(button0 == button1)
+assert(tostring(button0):find("Button") ~= nil)
+"#, None).unwrap()
+    }
+
+    #[test]
+    /// `ButtonState` should round-trip through JSON (the format the IPC
+    /// object-tree dump uses) with modifiers recorded by the same stable
+    /// names `set_modifiers` accepts, not raw bitflags.
+    fn button_state_serde_round_trip() {
+        use rustwlc::*;
+        use super::super::button::ButtonState;
+
+        let state = ButtonState {
+            button: 3,
+            modifiers: MOD_CAPS | MOD_SHIFT,
+            signal_id: 0
+        };
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains("\"Caps\""));
+        assert!(json.contains("\"Shift\""));
+
+        let restored: ButtonState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.button, state.button);
+        assert_eq!(restored.modifiers, state.modifiers);
+    }
+
+    #[test]
+    /// `Button::to_json` is the per-object building block a generic
+    /// object-tree walker (outside this tree) would call into.
+    fn button_to_json_test() {
+        use self::button::Button;
+        use self::object::Objectable;
+
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        lua.globals().set("a_button", button::allocator(&lua).unwrap());
+        let button = Button::cast(lua.globals().get::<_, Table>("a_button")
+                                  .unwrap().into()).unwrap();
+        button.set_button(4).unwrap();
+
+        let snapshot = button.to_json().unwrap();
+        assert_eq!(snapshot["class"], "button");
+        assert_eq!(snapshot["button"], 4);
+    }
+
+    #[test]
+    /// `to_json`/`apply_json` must round-trip a button's state into a
+    /// *different* live object, not just through a bare `ButtonState`.
+    fn button_json_round_trip_restores_live_object() {
+        use self::button::Button;
+        use self::object::Objectable;
+
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        lua.globals().set("source", button::allocator(&lua).unwrap());
+        lua.globals().set("dest", button::allocator(&lua).unwrap());
+        let source = Button::cast(lua.globals().get::<_, Table>("source")
+                                  .unwrap().into()).unwrap();
+        let dest = Button::cast(lua.globals().get::<_, Table>("dest")
+                                .unwrap().into()).unwrap();
+        source.set_button(4).unwrap();
+
+        let snapshot = source.to_json().unwrap();
+        dest.apply_json(&snapshot).unwrap();
+
+        assert_eq!(dest.button().unwrap(), rlua::Value::Integer(4));
+        assert_ne!(dest.signal_id().unwrap(), source.signal_id().unwrap());
+    }
+
+    #[test]
+    fn button_from_lua_rejects_non_button() {
+        use rlua::{FromLua, Value};
+        use self::button::Button;
+
+        let lua = Lua::new();
+        assert!(Button::from_lua(Value::Nil, &lua).is_err());
+        let not_a_button = lua.create_table();
+        assert!(Button::from_lua(Value::Table(not_a_button), &lua).is_err());
+    }
+
+    #[test]
+    fn object_from_lua_rejects_non_table() {
+        use rlua::{FromLua, Value};
+        use self::object::Object;
+
+        let lua = Lua::new();
+        assert!(Object::from_lua(Value::Nil, &lua).is_err());
+        // Unlike Button, any table converts into the untyped Object --
+        // class-specific validation happens only on the narrower cast.
+        let any_table = lua.create_table();
+        assert!(Object::from_lua(Value::Table(any_table), &lua).is_ok());
+    }
+
     #[test]
     /// Tests that setting the button index property updates the
     /// callback for all instances of button